@@ -1,13 +1,124 @@
-use crate::crypto::{decrypt_private_key, encrypt_private_key, EncryptedKey};
+use crate::crypto::{
+    decode_wif_private_key, decrypt_steem_memo, decrypt_with_key, derive_steem_keypair,
+    encrypt_steem_memo, encrypt_with_key, generate_master_key, load_master_key_from_keyring,
+    remove_master_key_from_keyring, sign_steem_transaction, steem_public_key_from_wif,
+    store_master_key_in_keyring, unwrap_master_key, wrap_master_key, CryptographyRoot,
+    EncryptedKey,
+};
+use crate::storage::{encrypted_key_storage_key, StorageManager};
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::State;
+use zeroize::Zeroizing;
+
+/// Storage key under which the `CryptographyRoot` blob is persisted.
+const CRYPTOGRAPHY_ROOT_STORAGE_KEY: &str = "cryptography_root";
+
+/// Holds the unwrapped master key for the lifetime of an unlocked session -
+/// the registry of live secrets that `clear_sensitive_data` wipes on
+/// lock/logout. The key is kept `Zeroizing` so replacing or clearing it
+/// actually overwrites the old bytes instead of just dropping the
+/// reference.
+pub struct SessionState {
+    master_key: Mutex<Option<Zeroizing<[u8; 32]>>>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        SessionState {
+            master_key: Mutex::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Result<Option<Zeroizing<[u8; 32]>>, String> {
+        let master_key = self
+            .master_key
+            .lock()
+            .map_err(|e| format!("Failed to acquire session lock: {}", e))?;
+        Ok(master_key.clone())
+    }
+
+    pub fn unlock(&self, master_key: Zeroizing<[u8; 32]>) -> Result<(), String> {
+        let mut slot = self
+            .master_key
+            .lock()
+            .map_err(|e| format!("Failed to acquire session lock: {}", e))?;
+        *slot = Some(master_key);
+        Ok(())
+    }
+
+    /// Wipe the session's master key from memory. Copies already returned
+    /// by `get` are separate `Zeroizing` values, wiped when their holders
+    /// drop them.
+    pub fn lock_session(&self) -> Result<(), String> {
+        let mut slot = self
+            .master_key
+            .lock()
+            .map_err(|e| format!("Failed to acquire session lock: {}", e))?;
+        *slot = None;
+        Ok(())
+    }
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn require_unlocked(session: &SessionState) -> Result<Zeroizing<[u8; 32]>, String> {
+    session
+        .get()?
+        .ok_or_else(|| "Unlock the wallet before performing this operation".to_string())
+}
+
+fn load_cryptography_root(storage: &StorageManager) -> Result<Option<CryptographyRoot>, String> {
+    match storage.get(CRYPTOGRAPHY_ROOT_STORAGE_KEY)? {
+        Some(value) => {
+            let root = serde_json::from_value(value)
+                .map_err(|e| format!("Corrupt cryptography root: {}", e))?;
+            Ok(Some(root))
+        }
+        None => Ok(None),
+    }
+}
+
+fn save_cryptography_root(storage: &StorageManager, root: &CryptographyRoot) -> Result<(), String> {
+    let value = serde_json::to_value(root)
+        .map_err(|e| format!("Failed to serialize cryptography root: {}", e))?;
+    storage.set(CRYPTOGRAPHY_ROOT_STORAGE_KEY.to_string(), value)
+}
+
+fn build_cryptography_root(
+    mode: &str,
+    password: Option<Zeroizing<String>>,
+    master_key: &[u8; 32],
+) -> Result<CryptographyRoot, String> {
+    match mode {
+        "password_protected" => {
+            let password = password
+                .ok_or_else(|| "Password is required for password_protected mode".to_string())?;
+            Ok(CryptographyRoot::PasswordProtected {
+                wrapped_key: wrap_master_key(master_key, &password)?,
+            })
+        }
+        "keyring" => {
+            store_master_key_in_keyring(master_key)?;
+            Ok(CryptographyRoot::Keyring)
+        }
+        "clear_text" => Ok(CryptographyRoot::ClearText {
+            master_key_hex: hex::encode(master_key),
+        }),
+        other => Err(format!("Unknown cryptography root mode: {}", other)),
+    }
+}
 
 /// Request to encrypt and store a private key
 #[derive(Serialize, Deserialize)]
 pub struct StoreKeyRequest {
-    pub key_type: String,        // 'active', 'owner', 'posting', 'memo'
-    pub private_key: String,
+    pub key_type: String, // 'active', 'owner', 'posting', 'memo'
+    pub private_key: Zeroizing<String>,
     pub username: String,
-    pub password: String,
 }
 
 /// Response with encrypted key data
@@ -22,7 +133,6 @@ pub struct StoreKeyResponse {
 pub struct RetrieveKeyRequest {
     pub key_type: String,
     pub username: String,
-    pub password: String,
 }
 
 /// Response with decrypted key
@@ -33,11 +143,16 @@ pub struct RetrieveKeyResponse {
     pub error: Option<String>,
 }
 
-/// Tauri command to store an encrypted private key
-/// The key is encrypted client-side and stored securely
+/// Tauri command to store an encrypted private key.
+/// The key is encrypted under the session's unlocked master key (not the
+/// password directly) and persisted under a composite `(username,
+/// key_type)` key so it can be found again after a restart.
 #[tauri::command]
-pub fn store_encrypted_key(request: StoreKeyRequest) -> Result<StoreKeyResponse, String> {
-    // Validate inputs
+pub fn store_encrypted_key(
+    request: StoreKeyRequest,
+    storage: State<StorageManager>,
+    session: State<SessionState>,
+) -> Result<StoreKeyResponse, String> {
     if request.private_key.is_empty() {
         return Ok(StoreKeyResponse {
             success: false,
@@ -45,18 +160,15 @@ pub fn store_encrypted_key(request: StoreKeyRequest) -> Result<StoreKeyResponse,
         });
     }
 
-    if request.password.is_empty() {
-        return Ok(StoreKeyResponse {
-            success: false,
-            message: "Password cannot be empty".to_string(),
-        });
-    }
+    let master_key = require_unlocked(&session)?;
+
+    match encrypt_with_key(&request.private_key, &master_key) {
+        Ok(encrypted_key) => {
+            let key = encrypted_key_storage_key(&request.username, &request.key_type);
+            let value = serde_json::to_value(&encrypted_key)
+                .map_err(|e| format!("Failed to serialize encrypted key: {}", e))?;
+            storage.set(key, value)?;
 
-    // Encrypt the private key
-    match encrypt_private_key(&request.private_key, &request.password) {
-        Ok(_encrypted_key) => {
-            // In production, store _encrypted_key to disk or secure storage
-            // For now, we just return success
             Ok(StoreKeyResponse {
                 success: true,
                 message: "Key stored securely".to_string(),
@@ -71,84 +183,318 @@ pub fn store_encrypted_key(request: StoreKeyRequest) -> Result<StoreKeyResponse,
 
 /// Tauri command to retrieve and decrypt a private key
 #[tauri::command]
-pub fn retrieve_encrypted_key(_request: RetrieveKeyRequest) -> Result<RetrieveKeyResponse, String> {
-    // In production, retrieve the encrypted key from storage
-    // For now, return error as it's not stored yet
-    Ok(RetrieveKeyResponse {
-        success: false,
-        private_key: None,
-        error: Some("Key not found in storage".to_string()),
-    })
+pub fn retrieve_encrypted_key(
+    request: RetrieveKeyRequest,
+    storage: State<StorageManager>,
+    session: State<SessionState>,
+) -> Result<RetrieveKeyResponse, String> {
+    let key = encrypted_key_storage_key(&request.username, &request.key_type);
+
+    let stored = match storage.get(&key)? {
+        Some(value) => value,
+        None => {
+            return Ok(RetrieveKeyResponse {
+                success: false,
+                private_key: None,
+                error: Some("Key not found in storage".to_string()),
+            })
+        }
+    };
+
+    let encrypted_key: EncryptedKey = serde_json::from_value(stored)
+        .map_err(|e| format!("Corrupt encrypted key in storage: {}", e))?;
+
+    let master_key = require_unlocked(&session)?;
+
+    match decrypt_with_key(&encrypted_key, &master_key) {
+        // This is the one place the decrypted key is deliberately allowed
+        // to leave its zeroizing wrapper: the caller asked to retrieve it.
+        Ok(private_key) => Ok(RetrieveKeyResponse {
+            success: true,
+            private_key: Some(private_key.to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(RetrieveKeyResponse {
+            success: false,
+            private_key: None,
+            error: Some(e),
+        }),
+    }
 }
 
-/// Tauri command to sign a transaction
-/// Private key is kept encrypted until signing, then discarded
+/// Tauri command to sign a transaction.
+/// `transaction_data` and `chain_id` are hex-encoded; `transaction_data` is
+/// expected to already be the graphene binary serialization of the
+/// transaction. The private key is decrypted only in Rust, never exposed
+/// to JS, and is wiped as soon as signing completes.
 #[tauri::command]
 pub fn sign_transaction(
     transaction_data: String,
+    chain_id: String,
     encrypted_key_data: String,
-    password: String,
+    session: State<SessionState>,
 ) -> Result<String, String> {
-    // Parse the encrypted key
     let encrypted_key: EncryptedKey = serde_json::from_str(&encrypted_key_data)
         .map_err(|e| format!("Invalid encrypted key format: {}", e))?;
 
-    // Decrypt the private key (only in Rust, never exposed to JS)
-    let _private_key = decrypt_private_key(&encrypted_key, &password)?;
+    let master_key = require_unlocked(&session)?;
+    let private_key = decrypt_with_key(&encrypted_key, &master_key)?;
 
-    // Sign the transaction (placeholder - real implementation would use dsteem)
-    // For now, just return a mock signature
-    let mock_signature = format!("signed_{}", hex::encode(transaction_data.as_bytes()));
+    let transaction_bytes = hex::decode(&transaction_data)
+        .map_err(|e| format!("Invalid transaction data: {}", e))?;
+    let chain_id_bytes =
+        hex::decode(&chain_id).map_err(|e| format!("Invalid chain id: {}", e))?;
+    let chain_id: [u8; 32] = chain_id_bytes
+        .try_into()
+        .map_err(|_| "Chain id must be 32 bytes".to_string())?;
 
-    // Private key is now dropped and cleaned from memory
-    Ok(mock_signature)
+    sign_steem_transaction(&chain_id, &transaction_bytes, &private_key)
 }
 
 /// Tauri command to verify a password
 #[tauri::command]
-pub fn verify_password(password: String) -> Result<bool, String> {
+pub fn verify_password(password: Zeroizing<String>) -> Result<bool, String> {
     // Basic validation - in production, verify against stored hash
     Ok(password.len() >= 8)
 }
 
-/// Tauri command to generate account keys from a master password
+/// Tauri command to generate account keys from a master password.
+/// Uses the standard Graphene/Steem derivation so the wallet can create or
+/// recover accounts offline, without talking to a node.
 #[tauri::command]
 pub fn generate_keys_from_password(
-    _username: String,
-    _password: String,
+    username: String,
+    password: Zeroizing<String>,
 ) -> Result<serde_json::Value, String> {
-    // In production, use proper key derivation
-    // For now, return placeholder keys
-    Ok(serde_json::json!({
-        "owner": {
-            "private": "5PLACEHOLDER_OWNER_KEY",
-            "public": "STM_PLACEHOLDER_OWNER_PUBLIC"
-        },
-        "active": {
-            "private": "5PLACEHOLDER_ACTIVE_KEY",
-            "public": "STM_PLACEHOLDER_ACTIVE_PUBLIC"
-        },
-        "posting": {
-            "private": "5PLACEHOLDER_POSTING_KEY",
-            "public": "STM_PLACEHOLDER_POSTING_PUBLIC"
-        },
-        "memo": {
-            "private": "5PLACEHOLDER_MEMO_KEY",
-            "public": "STM_PLACEHOLDER_MEMO_PUBLIC"
-        }
-    }))
+    let mut keys = serde_json::Map::new();
+    for role in ["owner", "active", "posting", "memo"] {
+        let (private_key, public_key) = derive_steem_keypair(&username, role, &password)?;
+        keys.insert(
+            role.to_string(),
+            serde_json::json!({
+                "private": private_key,
+                "public": public_key,
+            }),
+        );
+    }
+
+    Ok(serde_json::Value::Object(keys))
+}
+
+/// Tauri command to encrypt a private transfer memo for a recipient.
+/// `memo` should include any leading `#` the caller wants preserved in the
+/// plaintext (Steem convention marks encrypted memos with a `#` prefix on
+/// the outer encoded string, not the plaintext itself).
+#[tauri::command]
+pub fn encrypt_memo(
+    memo: String,
+    sender_memo_private_key: Zeroizing<String>,
+    recipient_memo_public_key: String,
+) -> Result<String, String> {
+    encrypt_steem_memo(&memo, &sender_memo_private_key, &recipient_memo_public_key)
+}
+
+/// Tauri command to decrypt a private transfer memo.
+#[tauri::command]
+pub fn decrypt_memo(
+    encoded_memo: String,
+    recipient_memo_private_key: Zeroizing<String>,
+    sender_memo_public_key: String,
+) -> Result<String, String> {
+    let memo = decrypt_steem_memo(
+        &encoded_memo,
+        &recipient_memo_private_key,
+        &sender_memo_public_key,
+    )?;
+    Ok(memo.to_string())
+}
+
+/// Response for `verify_private_key_format`. `public_key` is populated
+/// whenever the WIF is structurally valid and its payload is a usable
+/// secp256k1 secret key, so the UI can show which account/role a pasted
+/// key belongs to before import.
+#[derive(Serialize, Deserialize)]
+pub struct VerifyPrivateKeyResponse {
+    pub valid: bool,
+    pub public_key: Option<String>,
 }
 
-/// Tauri command to verify a private key format
+/// Tauri command to verify a private key format.
+/// Performs a real WIF validation (base58 decode, length, version byte and
+/// checksum) rather than the old `starts_with('5')` heuristic.
 #[tauri::command]
-pub fn verify_private_key_format(private_key: String) -> Result<bool, String> {
-    // Verify it starts with '5' (Steem private key format)
-    Ok(private_key.starts_with('5') && private_key.len() >= 50)
+pub fn verify_private_key_format(private_key: String) -> Result<VerifyPrivateKeyResponse, String> {
+    match decode_wif_private_key(&private_key) {
+        Ok(_) => Ok(VerifyPrivateKeyResponse {
+            valid: true,
+            public_key: steem_public_key_from_wif(&private_key).ok(),
+        }),
+        Err(_) => Ok(VerifyPrivateKeyResponse {
+            valid: false,
+            public_key: None,
+        }),
+    }
 }
 
 /// Tauri command to clear all sensitive data
 #[tauri::command]
-pub fn clear_sensitive_data() -> Result<(), String> {
-    // Clear any in-memory sensitive data
-    Ok(())
+pub fn clear_sensitive_data(session: State<SessionState>) -> Result<(), String> {
+    session.lock_session()
+}
+
+/// Request to initialize the cryptography root for a brand-new wallet.
+#[derive(Serialize, Deserialize)]
+pub struct InitCryptographyRootRequest {
+    pub mode: String, // "password_protected" | "keyring" | "clear_text"
+    pub password: Option<Zeroizing<String>>,
+}
+
+/// Request to unlock an already-initialized cryptography root.
+#[derive(Serialize, Deserialize)]
+pub struct UnlockCryptographyRootRequest {
+    pub password: Option<Zeroizing<String>>,
+}
+
+/// Request to change the password protecting the cryptography root.
+#[derive(Serialize, Deserialize)]
+pub struct ChangeCryptographyRootRequest {
+    pub old_password: Zeroizing<String>,
+    pub new_password: Zeroizing<String>,
+}
+
+/// Request to move the master key to a different protection mode.
+#[derive(Serialize, Deserialize)]
+pub struct SwitchCryptographyModeRequest {
+    pub mode: String,
+    pub password: Option<Zeroizing<String>>,
+}
+
+/// Generic success/failure response for cryptography root operations.
+#[derive(Serialize, Deserialize)]
+pub struct CryptographyRootResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Tauri command to initialize the cryptography root for a new wallet.
+/// Generates a fresh master key and protects it according to `mode`.
+#[tauri::command]
+pub fn init_cryptography_root(
+    request: InitCryptographyRootRequest,
+    storage: State<StorageManager>,
+    session: State<SessionState>,
+) -> Result<CryptographyRootResponse, String> {
+    if load_cryptography_root(&storage)?.is_some() {
+        return Ok(CryptographyRootResponse {
+            success: false,
+            message: "Cryptography root is already initialized".to_string(),
+        });
+    }
+
+    let master_key = generate_master_key();
+    let root = build_cryptography_root(&request.mode, request.password, &master_key)?;
+
+    save_cryptography_root(&storage, &root)?;
+    session.unlock(master_key)?;
+
+    Ok(CryptographyRootResponse {
+        success: true,
+        message: "Cryptography root initialized".to_string(),
+    })
+}
+
+/// Tauri command to unlock the wallet for the current session.
+#[tauri::command]
+pub fn unlock_cryptography_root(
+    request: UnlockCryptographyRootRequest,
+    storage: State<StorageManager>,
+    session: State<SessionState>,
+) -> Result<CryptographyRootResponse, String> {
+    let root = load_cryptography_root(&storage)?
+        .ok_or_else(|| "Cryptography root has not been initialized".to_string())?;
+
+    let master_key = match root {
+        CryptographyRoot::PasswordProtected { wrapped_key } => {
+            let password = request
+                .password
+                .ok_or_else(|| "Password is required to unlock".to_string())?;
+            unwrap_master_key(&wrapped_key, &password)?
+        }
+        CryptographyRoot::Keyring => load_master_key_from_keyring()?,
+        CryptographyRoot::ClearText { master_key_hex } => {
+            let bytes = hex::decode(&master_key_hex)
+                .map_err(|e| format!("Corrupt clear-text master key: {}", e))?;
+            let array: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| "Invalid master key length".to_string())?;
+            Zeroizing::new(array)
+        }
+    };
+
+    session.unlock(master_key)?;
+
+    Ok(CryptographyRootResponse {
+        success: true,
+        message: "Wallet unlocked".to_string(),
+    })
+}
+
+/// Tauri command to change the password protecting the cryptography root.
+/// Only re-wraps the root blob; per-account keys are untouched since they
+/// are encrypted under the master key, not the password.
+#[tauri::command]
+pub fn change_cryptography_root(
+    request: ChangeCryptographyRootRequest,
+    storage: State<StorageManager>,
+) -> Result<CryptographyRootResponse, String> {
+    let root = load_cryptography_root(&storage)?
+        .ok_or_else(|| "Cryptography root has not been initialized".to_string())?;
+
+    let wrapped_key = match root {
+        CryptographyRoot::PasswordProtected { wrapped_key } => wrapped_key,
+        _ => {
+            return Err(
+                "Changing the password is only supported in password_protected mode".to_string(),
+            )
+        }
+    };
+
+    let master_key = unwrap_master_key(&wrapped_key, &request.old_password)?;
+    let rewrapped = wrap_master_key(&master_key, &request.new_password)?;
+    save_cryptography_root(
+        &storage,
+        &CryptographyRoot::PasswordProtected {
+            wrapped_key: rewrapped,
+        },
+    )?;
+
+    Ok(CryptographyRootResponse {
+        success: true,
+        message: "Password changed".to_string(),
+    })
+}
+
+/// Tauri command to move the master key to a different protection mode.
+/// Requires the wallet to already be unlocked in this session.
+#[tauri::command]
+pub fn switch_cryptography_mode(
+    request: SwitchCryptographyModeRequest,
+    storage: State<StorageManager>,
+    session: State<SessionState>,
+) -> Result<CryptographyRootResponse, String> {
+    let master_key = require_unlocked(&session)?;
+    let root = build_cryptography_root(&request.mode, request.password, &master_key)?;
+
+    // Leaving keyring mode for something else should not leave the master
+    // key behind in the OS secure store.
+    if !matches!(root, CryptographyRoot::Keyring) {
+        let _ = remove_master_key_from_keyring();
+    }
+
+    save_cryptography_root(&storage, &root)?;
+
+    Ok(CryptographyRootResponse {
+        success: true,
+        message: "Cryptography mode updated".to_string(),
+    })
 }
@@ -1,99 +1,231 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use aes_gcm::{
     aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, PasswordHasher, Version,
-};
+use argon2::{Algorithm, Argon2, Version};
+use k256::ecdh::diffie_hellman;
+use k256::ecdsa::{hazmat::sign_prehash_rfc6979, RecoveryId, Signature, SigningKey};
+use k256::{PublicKey, Secp256k1, SecretKey};
 use rand::Rng;
+use ripemd::Ripemd160;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// Prefix Steem uses on base58-encoded public keys.
+const STEEM_PUBLIC_KEY_PREFIX: &str = "STM";
+
+/// Version byte Steem (and Bitcoin) WIF-encoded private keys start with.
+const WIF_VERSION_BYTE: u8 = 0x80;
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn ripemd160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(data).into()
+}
+
+/// Current `EncryptedKey` on-disk format version.
+const ENCRYPTED_KEY_VERSION: u32 = 1;
+
+/// The Argon2id cost parameters used to derive a key from a password.
+/// Recorded alongside each password-protected `EncryptedKey` so a future
+/// parameter upgrade can still decrypt keys written under the old ones.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Argon2Params {
+            m_cost: 19456,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
 
-/// Represents an encrypted private key with metadata
+/// Represents an encrypted private key with metadata.
+///
+/// `salt` and `params` are only present when the key was encrypted
+/// directly under a password (via `encrypt_private_key`); entries
+/// encrypted under a raw key (e.g. the master key, via `encrypt_with_key`)
+/// leave them `None` since no password derivation happened for them.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EncryptedKey {
+    pub version: u32,
     pub ciphertext: String,
     pub nonce: String,
     pub tag: String,
-    pub salt: String,
+    pub salt: Option<String>,
+    pub params: Option<Argon2Params>,
+}
+
+/// A master key wrapped for storage. Reuses the `EncryptedKey` envelope -
+/// the "private key" being wrapped is just the hex-encoded master key
+/// bytes.
+pub type WrappedMasterKey = EncryptedKey;
+
+/// Service/account names used to store the unwrapped master key in the OS
+/// secure store (Keychain on macOS, Credential Manager on Windows, Secret
+/// Service on Linux) via the `keyring` crate.
+const KEYRING_SERVICE: &str = "com.steemblocks.wallet";
+const KEYRING_ACCOUNT: &str = "master-key";
+
+/// Describes how the wallet's master key is protected at rest.
+///
+/// All per-account `EncryptedKey` entries are encrypted under the master
+/// key rather than directly under the user's password, so changing the
+/// password only needs to re-wrap this root - not every stored key.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum CryptographyRoot {
+    /// The master key is wrapped with an Argon2-derived password key.
+    PasswordProtected { wrapped_key: WrappedMasterKey },
+    /// The master key is stored unwrapped in the OS secure store.
+    Keyring,
+    /// The master key is kept as plain hex. Test-only - never use this in
+    /// a real wallet.
+    ClearText { master_key_hex: String },
+}
+
+/// Generate a fresh random 32-byte master key.
+pub fn generate_master_key() -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(rand::thread_rng().gen())
 }
 
-/// Initialize a new encryption key from a password
-pub fn derive_key_from_password(password: &str) -> Result<[u8; 32], String> {
-    let salt = SaltString::generate(rand::thread_rng());
+/// Wrap a master key with a password-derived key for storage.
+pub fn wrap_master_key(master_key: &[u8; 32], password: &str) -> Result<WrappedMasterKey, String> {
+    encrypt_private_key(&hex::encode(master_key), password)
+}
 
+/// Recover a master key previously wrapped with `wrap_master_key`.
+pub fn unwrap_master_key(
+    wrapped: &WrappedMasterKey,
+    password: &str,
+) -> Result<Zeroizing<[u8; 32]>, String> {
+    let hex_key = decrypt_private_key(wrapped, password)?;
+    let bytes =
+        hex::decode(hex_key.as_str()).map_err(|e| format!("Corrupt wrapped master key: {}", e))?;
+    let master_key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "Invalid master key length".to_string())?;
+    Ok(Zeroizing::new(master_key))
+}
+
+/// Store the master key, unwrapped, in the OS secure store.
+pub fn store_master_key_in_keyring(master_key: &[u8; 32]) -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access OS keyring: {}", e))?;
+    entry
+        .set_password(&hex::encode(master_key))
+        .map_err(|e| format!("Failed to store master key in OS keyring: {}", e))
+}
+
+/// Load the master key previously stored in the OS secure store.
+pub fn load_master_key_from_keyring() -> Result<Zeroizing<[u8; 32]>, String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access OS keyring: {}", e))?;
+    let hex_key = Zeroizing::new(
+        entry
+            .get_password()
+            .map_err(|e| format!("Failed to read master key from OS keyring: {}", e))?,
+    );
+    let bytes =
+        hex::decode(hex_key.as_str()).map_err(|e| format!("Corrupt keyring master key: {}", e))?;
+    let master_key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "Invalid master key length in keyring".to_string())?;
+    Ok(Zeroizing::new(master_key))
+}
+
+/// Remove the master key from the OS secure store, e.g. when switching away
+/// from `Keyring` mode. Missing entries are not an error.
+pub fn remove_master_key_from_keyring() -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access OS keyring: {}", e))?;
+    match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove master key from OS keyring: {}", e)),
+    }
+}
+
+/// Derive a 32-byte key from a password, salt and Argon2id cost parameters.
+///
+/// The salt must be supplied by the caller and stored alongside the
+/// ciphertext (see `EncryptedKey::salt`) so decryption can reconstruct the
+/// exact same key - generating a fresh salt on every call, as earlier
+/// versions of this function did, makes decryption impossible.
+pub fn derive_key_from_password(
+    password: &str,
+    salt: &[u8],
+    params: &Argon2Params,
+) -> Result<Zeroizing<[u8; 32]>, String> {
     let argon2 = Argon2::new(
         Algorithm::Argon2id,
         Version::V0x13,
-        argon2::Params::new(
-            19456,
-            2,
-            1,
-            Some(32),
-        )
-        .map_err(|e| format!("Failed to create Argon2 params: {}", e))?,
+        argon2::Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+            .map_err(|e| format!("Failed to create Argon2 params: {}", e))?,
     );
 
-    // Hash password to get key bytes
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt)
+    // Wrapped before filling so a failure partway through `hash_password_into`
+    // still zeroizes whatever was written when `key` drops.
+    let mut key = Zeroizing::new([0u8; 32]);
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key[..])
         .map_err(|e| format!("Password hashing failed: {}", e))?;
 
-    // Extract the hash as key material
-    let hash_string = password_hash.to_string();
-    let key_bytes = hash_string.as_bytes();
-
-    // Create a [u8; 32] from the hash
-    let mut key = [0u8; 32];
-    for (i, &byte) in key_bytes.iter().take(32).enumerate() {
-        key[i] = byte;
-    }
-
     Ok(key)
 }
 
-/// Encrypt a private key with a password
-pub fn encrypt_private_key(private_key: &str, password: &str) -> Result<EncryptedKey, String> {
-    // Generate random nonce
+/// Encrypt arbitrary plaintext under a raw 32-byte key (e.g. the unlocked
+/// master key). Account-level `EncryptedKey` entries use this directly so
+/// that re-wrapping the password only touches the master key, not every
+/// stored key.
+pub fn encrypt_with_key(plaintext: &str, key: &[u8; 32]) -> Result<EncryptedKey, String> {
     let mut rng = rand::thread_rng();
     let nonce_bytes: [u8; 12] = rng.gen();
     let nonce = Nonce::from_slice(&nonce_bytes);
 
-    // Derive key from password
-    let key = derive_key_from_password(password)?;
-    let cipher = Aes256Gcm::new(&key.into());
-
-    // Encrypt the private key
+    let cipher = Aes256Gcm::new(key.into());
     let ciphertext = cipher
         .encrypt(
             nonce,
             Payload {
-                msg: private_key.as_bytes(),
+                msg: plaintext.as_bytes(),
                 aad: b"",
             },
         )
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    // Generate salt for storage
-    let salt = SaltString::generate(rand::thread_rng());
-
     Ok(EncryptedKey {
+        version: ENCRYPTED_KEY_VERSION,
         ciphertext: hex::encode(&ciphertext),
         nonce: hex::encode(nonce.as_slice()),
         tag: String::new(), // Tag is embedded in AES-GCM ciphertext
-        salt: salt.to_string(),
+        salt: None,
+        params: None,
     })
 }
 
-/// Decrypt a private key with a password
-pub fn decrypt_private_key(
+/// Decrypt a value previously encrypted with `encrypt_with_key`. The
+/// returned plaintext is wrapped so it is wiped from memory as soon as the
+/// caller drops it, rather than lingering until the allocator reuses the
+/// page.
+pub fn decrypt_with_key(
     encrypted_key: &EncryptedKey,
-    password: &str,
-) -> Result<String, String> {
-    // Derive key from password
-    let key = derive_key_from_password(password)?;
-    let cipher = Aes256Gcm::new(&key.into());
+    key: &[u8; 32],
+) -> Result<Zeroizing<String>, String> {
+    let cipher = Aes256Gcm::new(key.into());
 
-    // Decode hex strings
     let ciphertext = hex::decode(&encrypted_key.ciphertext)
         .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
     let nonce_bytes = hex::decode(&encrypted_key.nonce)
@@ -105,18 +237,413 @@ pub fn decrypt_private_key(
 
     let nonce = Nonce::from_slice(&nonce_bytes);
 
-    // Decrypt
-    let plaintext = cipher
-        .decrypt(
-            nonce,
-            Payload {
-                msg: ciphertext.as_ref(),
-                aad: b"",
-            },
-        )
-        .map_err(|e| format!("Decryption failed: {}", e))?;
+    let plaintext_bytes = Zeroizing::new(
+        cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext.as_ref(),
+                    aad: b"",
+                },
+            )
+            .map_err(|e| format!("Decryption failed: {}", e))?,
+    );
+
+    let plaintext =
+        String::from_utf8(plaintext_bytes.to_vec()).map_err(|e| format!("Invalid UTF-8: {}", e))?;
+    Ok(Zeroizing::new(plaintext))
+}
+
+/// Encrypt a private key with a password. A fresh random salt is generated
+/// and stored in the returned `EncryptedKey` so the identical key can be
+/// re-derived at decryption time.
+pub fn encrypt_private_key(private_key: &str, password: &str) -> Result<EncryptedKey, String> {
+    let salt: [u8; 16] = rand::thread_rng().gen();
+    let params = Argon2Params::default();
+    let key = derive_key_from_password(password, &salt, &params)?;
+
+    let mut encrypted = encrypt_with_key(private_key, &key)?;
+    encrypted.salt = Some(hex::encode(salt));
+    encrypted.params = Some(params);
+    Ok(encrypted)
+}
+
+/// Decrypt a private key with a password, using the salt and Argon2
+/// parameters recorded in `encrypted_key`.
+pub fn decrypt_private_key(
+    encrypted_key: &EncryptedKey,
+    password: &str,
+) -> Result<Zeroizing<String>, String> {
+    let salt_hex = encrypted_key
+        .salt
+        .as_ref()
+        .ok_or_else(|| "Missing salt for password-based decryption".to_string())?;
+    let salt = hex::decode(salt_hex).map_err(|e| format!("Invalid salt: {}", e))?;
+    let params = encrypted_key
+        .params
+        .clone()
+        .ok_or_else(|| "Missing Argon2 parameters for password-based decryption".to_string())?;
+
+    let key = derive_key_from_password(password, &salt, &params)?;
+    decrypt_with_key(encrypted_key, &key)
+}
+
+/// Derive the 32-byte private key seed for one role of a Steem master
+/// password account, following the Graphene convention:
+/// `sha256(username + role + master_password)`.
+fn steem_private_key_seed(username: &str, role: &str, master_password: &str) -> [u8; 32] {
+    sha256(format!("{}{}{}", username, role, master_password).as_bytes())
+}
+
+/// WIF-encode a 32-byte secp256k1 private key seed: version byte `0x80`,
+/// the seed, then the first 4 bytes of `sha256(sha256(payload))` as a
+/// checksum, all base58-encoded.
+fn wif_encode_private_key(seed: &[u8; 32]) -> String {
+    let mut payload = Vec::with_capacity(1 + 32 + 4);
+    payload.push(WIF_VERSION_BYTE);
+    payload.extend_from_slice(seed);
+
+    let checksum = sha256(&sha256(&payload));
+    payload.extend_from_slice(&checksum[..4]);
+
+    bs58::encode(payload).into_string()
+}
+
+/// Derive the `STM`-prefixed compressed public key for a secp256k1 private
+/// key seed: the 33-byte compressed point, then the first 4 bytes of
+/// `ripemd160(pubkey)` as a checksum, base58-encoded.
+fn steem_public_key_from_seed(seed: &[u8; 32]) -> Result<String, String> {
+    let signing_key =
+        SigningKey::from_bytes(seed.into()).map_err(|e| format!("Invalid key seed: {}", e))?;
+    let compressed_point = signing_key.verifying_key().to_encoded_point(true);
+    let pubkey_bytes = compressed_point.as_bytes();
+
+    let checksum = ripemd160(pubkey_bytes);
+
+    let mut payload = Vec::with_capacity(pubkey_bytes.len() + 4);
+    payload.extend_from_slice(pubkey_bytes);
+    payload.extend_from_slice(&checksum[..4]);
+
+    Ok(format!(
+        "{}{}",
+        STEEM_PUBLIC_KEY_PREFIX,
+        bs58::encode(payload).into_string()
+    ))
+}
+
+/// Derive the `STM`-prefixed public key that corresponds to a WIF-encoded
+/// private key, validating the WIF along the way. Used to let the UI show
+/// which account/role a pasted key belongs to before import.
+pub(crate) fn steem_public_key_from_wif(wif: &str) -> Result<String, String> {
+    let seed = decode_wif_private_key(wif)?;
+    steem_public_key_from_seed(&seed)
+}
+
+/// Decode an `STM`-prefixed base58 public key into its 33-byte compressed
+/// secp256k1 point, verifying the ripemd160 checksum.
+fn decode_steem_public_key(public_key: &str) -> Result<[u8; 33], String> {
+    let encoded = public_key
+        .strip_prefix(STEEM_PUBLIC_KEY_PREFIX)
+        .ok_or_else(|| "Public key is missing the STM prefix".to_string())?;
+    let decoded = bs58::decode(encoded)
+        .into_vec()
+        .map_err(|e| format!("Invalid base58: {}", e))?;
+
+    if decoded.len() != 37 {
+        return Err("Invalid public key length".to_string());
+    }
+
+    let (pubkey_bytes, checksum) = decoded.split_at(33);
+    let expected_checksum = ripemd160(pubkey_bytes);
+    if expected_checksum[..4] != *checksum {
+        return Err("Public key checksum mismatch".to_string());
+    }
+
+    pubkey_bytes
+        .try_into()
+        .map_err(|_| "Invalid public key payload length".to_string())
+}
+
+/// Derive the WIF private key and `STM` public key for one role of a Steem
+/// master-password account.
+pub fn derive_steem_keypair(
+    username: &str,
+    role: &str,
+    master_password: &str,
+) -> Result<(String, String), String> {
+    let seed = steem_private_key_seed(username, role, master_password);
+    let private_key = wif_encode_private_key(&seed);
+    let public_key = steem_public_key_from_seed(&seed)?;
+    Ok((private_key, public_key))
+}
+
+/// Decode a WIF-encoded private key into its 32-byte payload, verifying
+/// the version byte and checksum along the way. The payload is sensitive
+/// key material, so it is wrapped to be wiped on drop.
+pub(crate) fn decode_wif_private_key(wif: &str) -> Result<Zeroizing<[u8; 32]>, String> {
+    let decoded = bs58::decode(wif)
+        .into_vec()
+        .map_err(|e| format!("Invalid base58: {}", e))?;
+
+    if decoded.len() != 37 {
+        return Err("Invalid WIF length".to_string());
+    }
+
+    let (payload, checksum) = decoded.split_at(33);
+    if payload[0] != WIF_VERSION_BYTE {
+        return Err("Invalid WIF version byte".to_string());
+    }
+
+    let expected_checksum = sha256(&sha256(payload));
+    if expected_checksum[..4] != *checksum {
+        return Err("WIF checksum mismatch".to_string());
+    }
+
+    let seed: [u8; 32] = payload[1..]
+        .try_into()
+        .map_err(|_| "Invalid WIF payload length".to_string())?;
+    Ok(Zeroizing::new(seed))
+}
+
+/// Graphene's `is_canonical` for one 32-byte signature component (`r` or
+/// `s`): the high bit of the first byte must be clear, *and* the first byte
+/// must not be an unnecessary leading zero (a zero first byte is only
+/// canonical if it was required to keep the second byte's high bit clear).
+/// Steem nodes reject signatures that fail this, even though they still
+/// recover the correct pubkey, so it's stricter than "high bit clear".
+fn is_canonical_signature_component(bytes: &[u8; 32]) -> bool {
+    bytes[0] < 0x80 && !(bytes[0] == 0 && bytes[1] < 0x80)
+}
+
+/// Sign a serialized Steem transaction with a WIF-encoded private key.
+///
+/// `transaction_bytes` must already be the graphene binary serialization of
+/// the transaction (`ref_block_num`, `ref_block_prefix`, `expiration`,
+/// `operations`, ...) - this function only handles the chain-id prefix,
+/// hashing and signing. The digest is `sha256(chain_id || transaction_bytes)`,
+/// signed with a canonical (per `is_canonical_signature_component`)
+/// recoverable ECDSA signature as Steem requires, encoded as the 65-byte
+/// compact form `[recovery_id + 31, r, s]`.
+pub fn sign_steem_transaction(
+    chain_id: &[u8; 32],
+    transaction_bytes: &[u8],
+    private_key_wif: &str,
+) -> Result<String, String> {
+    let seed = decode_wif_private_key(private_key_wif)?;
+    let signing_key = SigningKey::from_bytes((&*seed).into())
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+
+    let mut digest_input = Vec::with_capacity(chain_id.len() + transaction_bytes.len());
+    digest_input.extend_from_slice(chain_id);
+    digest_input.extend_from_slice(transaction_bytes);
+    let digest = sha256(&digest_input);
+
+    // Steem requires a "canonical" signature (see
+    // `is_canonical_signature_component`). Retry with different
+    // deterministic-nonce entropy until that holds, rather than ever using
+    // a non-deterministic nonce.
+    let result = (0u8..=255).find_map(|attempt| {
+        let ad = [attempt];
+        let (signature, recovery_id): (Signature, RecoveryId) =
+            sign_prehash_rfc6979::<Secp256k1, Sha256>(&signing_key, &digest, &ad).ok()?;
+
+        let r_bytes = signature.r().to_bytes();
+        let s_bytes = signature.s().to_bytes();
+        if is_canonical_signature_component(&r_bytes.into())
+            && is_canonical_signature_component(&s_bytes.into())
+        {
+            let mut compact = [0u8; 65];
+            compact[0] = recovery_id.to_byte() + 31;
+            compact[1..33].copy_from_slice(&r_bytes);
+            compact[33..65].copy_from_slice(&s_bytes);
+            Some(compact)
+        } else {
+            None
+        }
+    });
+
+    // `seed` is `Zeroizing`, so it is wiped here when it drops, whether we
+    // found a canonical signature or every attempt failed.
+    result
+        .map(hex::encode)
+        .ok_or_else(|| "Failed to produce a canonical signature".to_string())
+}
+
+/// Compute the shared secret used for Steem memo encryption: the x
+/// coordinate of the ECDH point between one side's memo private key and
+/// the other side's raw 33-byte memo public key point. ECDH is symmetric,
+/// so the sender computing `(sender_priv, recipient_pub)` and the recipient
+/// computing `(recipient_priv, sender_pub)` arrive at the same secret.
+fn memo_shared_secret_raw(
+    private_key_wif: &str,
+    pubkey_bytes: &[u8; 33],
+) -> Result<Zeroizing<[u8; 32]>, String> {
+    let seed = decode_wif_private_key(private_key_wif)?;
+    let secret_key =
+        SecretKey::from_slice(&*seed).map_err(|e| format!("Invalid private key: {}", e))?;
+
+    let public_key = PublicKey::from_sec1_bytes(pubkey_bytes)
+        .map_err(|e| format!("Invalid public key: {}", e))?;
 
-    String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8: {}", e))
+    let shared = diffie_hellman(secret_key.to_nonzero_scalar(), public_key.as_affine());
+    Ok(Zeroizing::new(shared.raw_secret_bytes().as_slice().try_into().unwrap()))
+}
+
+/// Derive the AES-256-CBC key, IV and checksum Steem uses for memo
+/// encryption from an ECDH shared secret and the memo's nonce. Steem folds
+/// the nonce into the KDF (rather than using it as inert payload, which
+/// would make the key/IV - and therefore the ciphertext - identical every
+/// time the same two accounts exchange the same memo):
+/// `encryption_key = sha512(nonce_le ‖ sha512(ecdh_x))`. The memo's `check`
+/// field is the first 4 bytes of `sha256(encryption_key)`, so it
+/// authenticates that the reader derived the right key rather than
+/// authenticating the ciphertext bytes.
+fn memo_encryption_key(shared_secret: &[u8; 32], nonce: u64) -> ([u8; 32], [u8; 16], [u8; 4]) {
+    let inner = Sha512::digest(shared_secret);
+
+    let mut seed = Vec::with_capacity(8 + inner.len());
+    seed.extend_from_slice(&nonce.to_le_bytes());
+    seed.extend_from_slice(&inner);
+    let encryption_key = Sha512::digest(&seed);
+
+    let mut key = [0u8; 32];
+    let mut iv = [0u8; 16];
+    let mut check = [0u8; 4];
+    key.copy_from_slice(&encryption_key[0..32]);
+    iv.copy_from_slice(&encryption_key[32..48]);
+    check.copy_from_slice(&sha256(&encryption_key)[..4]);
+    (key, iv, check)
+}
+
+/// Encode `value` as an unsigned LEB128 varint (7 bits per byte, high bit
+/// as the continuation flag) - the same scheme graphene uses for a
+/// `VString`'s length prefix. Steem frames memo plaintext as
+/// `varint(len) ‖ utf8_bytes` before encrypting, so a real client can pull
+/// the text back out of the decrypted buffer without relying on padding.
+fn write_varint(value: usize, out: &mut Vec<u8>) {
+    let mut value = value as u64;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            out.push(byte | 0x80);
+        } else {
+            out.push(byte);
+            break;
+        }
+    }
+}
+
+/// Decode a varint written by `write_varint` from the front of `bytes`,
+/// returning the value and the number of bytes it occupied.
+fn read_varint(bytes: &[u8]) -> Result<(usize, usize), String> {
+    let mut value: u64 = 0;
+    for (i, &byte) in bytes.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value as usize, i + 1));
+        }
+    }
+    Err("Malformed memo length prefix".to_string())
+}
+
+/// Encrypt a memo the way Steem does. The message is
+/// `{from_pub, to_pub, nonce, check, encrypted}`, matching the wire format
+/// real Steem clients (Steemit, Keychain, beem) expect, so memos produced
+/// here can be read by - and memos from them read by - this wallet:
+///
+/// - `from_pub`/`to_pub`: the raw 33-byte compressed public key points of
+///   the sender and recipient.
+/// - `nonce`: a random 8-byte value, little-endian, folded into the AES key
+///   derivation (see `memo_encryption_key`) so identical memos between the
+///   same two accounts never produce identical ciphertext.
+/// - `check`: the first 4 bytes of `sha256(encryption_key)`, authenticating
+///   that the correct key/IV were derived rather than the ciphertext.
+/// - `encrypted`: `varint(memo.len()) ‖ memo`, AES-256-CBC encrypted under
+///   the derived key/IV - graphene's `VString` framing, not the raw bytes.
+///
+/// The whole message is base58-encoded with a leading `#`.
+pub fn encrypt_steem_memo(
+    memo: &str,
+    sender_memo_private_key_wif: &str,
+    recipient_memo_public_key: &str,
+) -> Result<String, String> {
+    let sender_memo_public_key = steem_public_key_from_wif(sender_memo_private_key_wif)?;
+    let from_pub = decode_steem_public_key(&sender_memo_public_key)?;
+    let to_pub = decode_steem_public_key(recipient_memo_public_key)?;
+
+    let shared_secret = memo_shared_secret_raw(sender_memo_private_key_wif, &to_pub)?;
+    let nonce: u64 = rand::thread_rng().gen();
+    let (aes_key, iv, checksum) = memo_encryption_key(&shared_secret, nonce);
+
+    let mut framed_memo = Vec::with_capacity(10 + memo.len());
+    write_varint(memo.len(), &mut framed_memo);
+    framed_memo.extend_from_slice(memo.as_bytes());
+
+    let ciphertext = Aes256CbcEnc::new((&aes_key).into(), (&iv).into())
+        .encrypt_padded_vec_mut::<Pkcs7>(&framed_memo);
+
+    let mut payload = Vec::with_capacity(33 + 33 + 8 + 4 + ciphertext.len());
+    payload.extend_from_slice(&from_pub);
+    payload.extend_from_slice(&to_pub);
+    payload.extend_from_slice(&nonce.to_le_bytes());
+    payload.extend_from_slice(&checksum);
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(format!("#{}", bs58::encode(payload).into_string()))
+}
+
+/// Decrypt a memo previously produced by `encrypt_steem_memo` (or by a
+/// real Steem client), using the recipient's memo private key. The
+/// sender's public key is read back out of the memo itself and cross
+/// checked against `sender_memo_public_key` so a caller can't be fed a
+/// memo that claims to be from someone else.
+pub fn decrypt_steem_memo(
+    encoded_memo: &str,
+    recipient_memo_private_key_wif: &str,
+    sender_memo_public_key: &str,
+) -> Result<Zeroizing<String>, String> {
+    let encoded = encoded_memo
+        .strip_prefix('#')
+        .ok_or_else(|| "Memo is missing the '#' prefix".to_string())?;
+    let decoded = bs58::decode(encoded)
+        .into_vec()
+        .map_err(|e| format!("Invalid base58: {}", e))?;
+
+    if decoded.len() < 33 + 33 + 8 + 4 {
+        return Err("Memo is too short".to_string());
+    }
+    let (from_pub, rest) = decoded.split_at(33);
+    let (_to_pub, rest) = rest.split_at(33);
+    let (nonce_bytes, rest) = rest.split_at(8);
+    let (checksum, ciphertext) = rest.split_at(4);
+
+    let expected_from_pub = decode_steem_public_key(sender_memo_public_key)?;
+    if from_pub != expected_from_pub {
+        return Err("Memo sender public key does not match the embedded key".to_string());
+    }
+
+    let nonce = u64::from_le_bytes(nonce_bytes.try_into().unwrap());
+    let from_pub: [u8; 33] = from_pub.try_into().unwrap();
+    let shared_secret = memo_shared_secret_raw(recipient_memo_private_key_wif, &from_pub)?;
+    let (aes_key, iv, expected_checksum) = memo_encryption_key(&shared_secret, nonce);
+    if expected_checksum != *checksum {
+        return Err("Memo checksum mismatch".to_string());
+    }
+
+    let plaintext = Aes256CbcDec::new((&aes_key).into(), (&iv).into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|e| format!("Memo decryption failed: {}", e))?;
+
+    let (memo_len, prefix_len) = read_varint(&plaintext)?;
+    let body_end = prefix_len
+        .checked_add(memo_len)
+        .ok_or_else(|| "Memo length prefix overruns the decrypted data".to_string())?;
+    let body = plaintext
+        .get(prefix_len..body_end)
+        .ok_or_else(|| "Memo length prefix overruns the decrypted data".to_string())?;
+    let memo = String::from_utf8(body.to_vec()).map_err(|e| format!("Invalid UTF-8 in memo: {}", e))?;
+    Ok(Zeroizing::new(memo))
 }
 
 #[cfg(test)]
@@ -131,7 +658,7 @@ mod tests {
         let encrypted = encrypt_private_key(private_key, password).unwrap();
         let decrypted = decrypt_private_key(&encrypted, password).unwrap();
 
-        assert_eq!(private_key, decrypted);
+        assert_eq!(private_key, decrypted.as_str());
     }
 
     #[test]
@@ -145,4 +672,165 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_wif_round_trip() {
+        let seed = [7u8; 32];
+        let wif = wif_encode_private_key(&seed);
+
+        assert!(wif.starts_with('5'));
+        assert_eq!(*decode_wif_private_key(&wif).unwrap(), seed);
+    }
+
+    #[test]
+    fn test_derive_steem_keypair_shape() {
+        let (private_key, public_key) =
+            derive_steem_keypair("alice", "posting", "correct horse battery staple").unwrap();
+
+        assert!(private_key.starts_with('5'));
+        assert!(public_key.starts_with("STM"));
+        assert_eq!(decode_wif_private_key(&private_key).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_sign_steem_transaction_is_canonical() {
+        let seed = [3u8; 32];
+        let private_key_wif = wif_encode_private_key(&seed);
+        let chain_id = [0u8; 32];
+        let transaction_bytes = b"test-transaction-body";
+
+        let signature_hex =
+            sign_steem_transaction(&chain_id, transaction_bytes, &private_key_wif).unwrap();
+        let signature = hex::decode(&signature_hex).unwrap();
+
+        assert_eq!(signature.len(), 65);
+        let r: [u8; 32] = signature[1..33].try_into().unwrap();
+        let s: [u8; 32] = signature[33..65].try_into().unwrap();
+        assert!(is_canonical_signature_component(&r), "r must be canonical");
+        assert!(is_canonical_signature_component(&s), "s must be canonical");
+    }
+
+    #[test]
+    fn test_is_canonical_signature_component_rejects_unnecessary_leading_zero() {
+        let mut leading_zero = [0u8; 32];
+        leading_zero[1] = 0x01; // high bit of byte[1] clear - the leading zero wasn't needed
+        assert!(!is_canonical_signature_component(&leading_zero));
+
+        let mut needed_zero = [0u8; 32];
+        needed_zero[1] = 0x80; // high bit of byte[1] set - the leading zero is required
+        assert!(is_canonical_signature_component(&needed_zero));
+
+        let mut high_bit_set = [0u8; 32];
+        high_bit_set[0] = 0x80;
+        assert!(!is_canonical_signature_component(&high_bit_set));
+    }
+
+    #[test]
+    fn test_sign_steem_transaction_recovers_signer_pubkey() {
+        use k256::ecdsa::signature::hazmat::PrehashVerifier;
+        use k256::ecdsa::VerifyingKey;
+
+        let seed = [3u8; 32];
+        let private_key_wif = wif_encode_private_key(&seed);
+        let chain_id = [9u8; 32];
+        let transaction_bytes = b"test-transaction-body";
+
+        let signature_hex =
+            sign_steem_transaction(&chain_id, transaction_bytes, &private_key_wif).unwrap();
+        let signature_bytes = hex::decode(&signature_hex).unwrap();
+
+        let recovery_id = RecoveryId::from_byte(signature_bytes[0] - 31).unwrap();
+        let signature = Signature::from_scalars(
+            <[u8; 32]>::try_from(&signature_bytes[1..33]).unwrap(),
+            <[u8; 32]>::try_from(&signature_bytes[33..65]).unwrap(),
+        )
+        .unwrap();
+
+        let mut digest_input = Vec::with_capacity(chain_id.len() + transaction_bytes.len());
+        digest_input.extend_from_slice(&chain_id);
+        digest_input.extend_from_slice(transaction_bytes);
+        let digest = sha256(&digest_input);
+
+        let recovered = VerifyingKey::recover_from_prehash(&digest, &signature, recovery_id)
+            .expect("signature must recover a valid public key");
+
+        let expected = SigningKey::from_bytes((&seed).into())
+            .unwrap()
+            .verifying_key()
+            .to_owned();
+        assert_eq!(recovered, expected, "recovered key must match the signer");
+
+        recovered
+            .verify_prehash(&digest, &signature)
+            .expect("recovered key must verify the signature over the digest");
+    }
+
+    #[test]
+    fn test_memo_round_trip() {
+        let (sender_private, sender_public) =
+            derive_steem_keypair("alice", "memo", "alice-password").unwrap();
+        let (recipient_private, recipient_public) =
+            derive_steem_keypair("bob", "memo", "bob-password").unwrap();
+
+        let memo = "#thank you for the coffee";
+        let encrypted = encrypt_steem_memo(memo, &sender_private, &recipient_public).unwrap();
+        assert!(encrypted.starts_with('#'));
+
+        let decrypted = decrypt_steem_memo(&encrypted, &recipient_private, &sender_public).unwrap();
+        assert_eq!(memo, decrypted.as_str());
+    }
+
+    #[test]
+    fn test_memo_encryption_is_randomized() {
+        // The nonce is folded into the key derivation, so encrypting the
+        // same memo twice must not produce byte-identical ciphertext.
+        let (sender_private, _) = derive_steem_keypair("alice", "memo", "alice-password").unwrap();
+        let (_, recipient_public) = derive_steem_keypair("bob", "memo", "bob-password").unwrap();
+
+        let memo = "#thank you for the coffee";
+        let first = encrypt_steem_memo(memo, &sender_private, &recipient_public).unwrap();
+        let second = encrypt_steem_memo(memo, &sender_private, &recipient_public).unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_memo_rejects_mismatched_sender() {
+        let (sender_private, _) = derive_steem_keypair("alice", "memo", "alice-password").unwrap();
+        let (_, recipient_public) = derive_steem_keypair("bob", "memo", "bob-password").unwrap();
+        let (recipient_private, _) = derive_steem_keypair("bob", "memo", "bob-password").unwrap();
+        let (_, impostor_public) = derive_steem_keypair("carol", "memo", "carol-password").unwrap();
+
+        let memo = "#thank you for the coffee";
+        let encrypted = encrypt_steem_memo(memo, &sender_private, &recipient_public).unwrap();
+
+        let result = decrypt_steem_memo(&encrypted, &recipient_private, &impostor_public);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_memo_encryption_roundtrips_past_the_varint_byte_boundary() {
+        // A memo longer than 127 bytes pushes the length prefix past one
+        // varint byte, exercising the continuation-bit path on both ends.
+        let (sender_private, _) = derive_steem_keypair("alice", "memo", "alice-password").unwrap();
+        let (recipient_private, recipient_public) =
+            derive_steem_keypair("bob", "memo", "bob-password").unwrap();
+        let (_, sender_public) = derive_steem_keypair("alice", "memo", "alice-password").unwrap();
+
+        let memo = format!("#{}", "x".repeat(200));
+        let encrypted = encrypt_steem_memo(&memo, &sender_private, &recipient_public).unwrap();
+        let decrypted = decrypt_steem_memo(&encrypted, &recipient_private, &sender_public).unwrap();
+        assert_eq!(memo, decrypted.as_str());
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0usize, 1, 127, 128, 300, 16384, 2_000_000] {
+            let mut buf = Vec::new();
+            write_varint(value, &mut buf);
+            let (decoded, len) = read_varint(&buf).unwrap();
+            assert_eq!(decoded, value);
+            assert_eq!(len, buf.len());
+        }
+    }
 }
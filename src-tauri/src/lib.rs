@@ -3,13 +3,28 @@ mod crypto;
 mod storage;
 
 use commands::*;
-use storage::StorageManager;
+use storage::{StorageConfig, StorageManager};
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .manage(StorageManager::new())
+        .setup(|app| {
+            // Persist wallet data (encrypted key blobs, settings) under the
+            // app's data dir so it survives a restart, instead of the old
+            // in-memory-only store. The backend is picked via `StorageConfig`
+            // (overridable with `STEEM_WALLET_STORAGE_BACKEND`), not hardcoded.
+            let data_dir = app
+                .path()
+                .app_data_dir()
+                .expect("failed to resolve app data dir");
+            let storage = StorageManager::from_config(StorageConfig::from_env(), data_dir)
+                .expect("failed to initialize storage backend");
+            app.manage(storage);
+            app.manage(SessionState::new());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Storage commands
             storage::storage_set,
@@ -20,10 +35,17 @@ pub fn run() {
             store_encrypted_key,
             retrieve_encrypted_key,
             sign_transaction,
+            encrypt_memo,
+            decrypt_memo,
             verify_password,
             generate_keys_from_password,
             verify_private_key_format,
             clear_sensitive_data,
+            // Cryptography root commands
+            init_cryptography_root,
+            unlock_cryptography_root,
+            change_cryptography_root,
+            switch_cryptography_mode,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,22 +1,95 @@
+use crate::crypto::{decrypt_with_key, encrypt_with_key, EncryptedKey};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tauri::State;
+use zeroize::Zeroizing;
 
-/// In-memory storage for wallet data
-/// In production, use tauri-plugin-store for persistent storage
-pub struct StorageManager {
+/// Current on-disk schema version for the file-backed storage format.
+/// Bump this and add a migration path in `FileStorage::load` if the
+/// envelope shape ever changes.
+///
+/// Bumped from 1 to 2 when the file switched from plaintext `entries` to
+/// an encrypted `envelope`. `FileStorage::load` migrates a version-1 file
+/// in place the first time it's opened.
+const STORAGE_FORMAT_VERSION: u32 = 2;
+
+/// Service/account under which `FileStorage`'s envelope-encryption key is
+/// kept in the OS secure store. Deliberately a separate identity from the
+/// wallet's master key (see `crypto::KEYRING_SERVICE`/`KEYRING_ACCOUNT`):
+/// the storage file has to be decryptable before the wallet is unlocked
+/// (the `CryptographyRoot` that tells us *how* to unlock lives inside it),
+/// so its key can't depend on the user's password.
+const STORAGE_KEYRING_SERVICE: &str = "com.steemblocks.wallet";
+const STORAGE_KEYRING_ACCOUNT: &str = "storage-encryption-key";
+
+/// Load this machine's `FileStorage` encryption key from the OS secure
+/// store, generating and persisting a fresh one on first use.
+fn storage_encryption_key() -> Result<Zeroizing<[u8; 32]>, String> {
+    let entry = keyring::Entry::new(STORAGE_KEYRING_SERVICE, STORAGE_KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access OS keyring: {}", e))?;
+
+    match entry.get_password() {
+        Ok(hex_key) => {
+            let bytes = hex::decode(&hex_key)
+                .map_err(|e| format!("Corrupt storage encryption key: {}", e))?;
+            let key: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| "Invalid storage encryption key length".to_string())?;
+            Ok(Zeroizing::new(key))
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key: Zeroizing<[u8; 32]> = Zeroizing::new(rand::thread_rng().gen());
+            entry
+                .set_password(&hex::encode(&*key))
+                .map_err(|e| format!("Failed to store storage encryption key: {}", e))?;
+            Ok(key)
+        }
+        Err(e) => Err(format!("Failed to read storage encryption key: {}", e)),
+    }
+}
+
+/// A pluggable storage backend for wallet data.
+///
+/// Keeping this behind a trait (rather than a concrete `HashMap`) means the
+/// app can swap in new backends - an OS keyring, a remote vault, etc. -
+/// without touching any call site. Implementations must be `Send + Sync` so
+/// a backend can live behind `Arc<dyn Storage + Send + Sync>` in
+/// `StorageManager` and be shared across Tauri's worker threads.
+pub trait Storage: Send + Sync {
+    fn set(&self, key: String, value: Value) -> Result<(), String>;
+    fn get(&self, key: &str) -> Result<Option<Value>, String>;
+    fn remove(&self, key: &str) -> Result<(), String>;
+    fn clear(&self) -> Result<(), String>;
+}
+
+/// In-memory storage for wallet data.
+/// Data does not survive process exit - useful for tests and for the
+/// `ClearText` cryptography root, but not for real wallet usage.
+pub struct MemoryStorage {
     data: Mutex<HashMap<String, Value>>,
 }
 
-impl StorageManager {
+impl MemoryStorage {
     pub fn new() -> Self {
-        StorageManager {
+        MemoryStorage {
             data: Mutex::new(HashMap::new()),
         }
     }
+}
 
-    pub fn set(&self, key: String, value: Value) -> Result<(), String> {
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn set(&self, key: String, value: Value) -> Result<(), String> {
         let mut data = self
             .data
             .lock()
@@ -25,7 +98,7 @@ impl StorageManager {
         Ok(())
     }
 
-    pub fn get(&self, key: &str) -> Result<Option<Value>, String> {
+    fn get(&self, key: &str) -> Result<Option<Value>, String> {
         let data = self
             .data
             .lock()
@@ -33,7 +106,7 @@ impl StorageManager {
         Ok(data.get(key).cloned())
     }
 
-    pub fn remove(&self, key: &str) -> Result<(), String> {
+    fn remove(&self, key: &str) -> Result<(), String> {
         let mut data = self
             .data
             .lock()
@@ -42,7 +115,7 @@ impl StorageManager {
         Ok(())
     }
 
-    pub fn clear(&self) -> Result<(), String> {
+    fn clear(&self) -> Result<(), String> {
         let mut data = self
             .data
             .lock()
@@ -52,9 +125,277 @@ impl StorageManager {
     }
 }
 
+/// The on-disk envelope written by `FileStorage`: the key/value map,
+/// AES-256-GCM encrypted under `storage_encryption_key()`, so nothing in
+/// the store - including a `ClearText`-mode `CryptographyRoot` - is ever
+/// written to disk in the clear. Versioned so a future schema change can
+/// migrate old files instead of failing to load them.
+#[derive(Serialize, Deserialize)]
+struct StorageFile {
+    version: u32,
+    envelope: EncryptedKey,
+}
+
+/// Persistent storage that mirrors the key/value map, encrypted, to a
+/// single file under the app data dir.
+///
+/// Every write rewrites the whole file via a temp-file-then-rename so a
+/// crash mid-write can never leave a half-written, corrupt store behind.
+/// The envelope encryption here is independent of any per-value encryption
+/// callers do themselves (e.g. `EncryptedKey` blobs produced by
+/// `crypto::encrypt_private_key`) - defense in depth, not a substitute.
+pub struct FileStorage {
+    path: PathBuf,
+    data: Mutex<HashMap<String, Value>>,
+    /// Resolved once in `new()` so `load`/`persist` don't round-trip the OS
+    /// keyring on every single read/write.
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let key = storage_encryption_key()?;
+        let (data, needs_migration) = Self::load(&path, &key)?;
+        let storage = FileStorage {
+            path,
+            data: Mutex::new(data),
+            key,
+        };
+        if needs_migration {
+            // Migrated from the pre-encryption version-1 plaintext format -
+            // rewrite immediately so the file is protected at rest from
+            // here on, rather than waiting for the next unrelated write.
+            let data = storage
+                .data
+                .lock()
+                .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+            storage.persist(&data)?;
+        }
+        Ok(storage)
+    }
+
+    /// Loads the entries at `path`, migrating a pre-encryption version-1
+    /// plaintext file in place. Returns whether a migration happened, so
+    /// the caller can immediately persist the now-current version-2
+    /// envelope instead of leaving the old plaintext file on disk.
+    fn load(path: &Path, key: &[u8; 32]) -> Result<(HashMap<String, Value>, bool), String> {
+        if !path.exists() {
+            return Ok((HashMap::new(), false));
+        }
+
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read storage file: {}", e))?;
+
+        // Check the version against a loosely-typed parse before the
+        // strict `StorageFile` deserialization below, so a file written by
+        // an older, incompatible envelope shape can be dispatched to the
+        // right parser instead of hitting an opaque "missing field" error.
+        #[derive(Deserialize)]
+        struct VersionOnly {
+            version: u32,
+        }
+        let version_only: VersionOnly = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Failed to parse storage file: {}", e))?;
+
+        if version_only.version == 1 {
+            #[derive(Deserialize)]
+            struct StorageFileV1 {
+                entries: HashMap<String, Value>,
+            }
+            let file: StorageFileV1 = serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Failed to parse version-1 storage file: {}", e))?;
+            return Ok((file.entries, true));
+        }
+
+        if version_only.version != STORAGE_FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported storage format version: {} (expected {})",
+                version_only.version, STORAGE_FORMAT_VERSION
+            ));
+        }
+
+        let file: StorageFile = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Failed to parse storage file: {}", e))?;
+
+        let plaintext = decrypt_with_key(&file.envelope, key)?;
+        let entries: HashMap<String, Value> = serde_json::from_str(&plaintext)
+            .map_err(|e| format!("Failed to parse decrypted storage: {}", e))?;
+
+        Ok((entries, false))
+    }
+
+    fn persist(&self, entries: &HashMap<String, Value>) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create storage directory: {}", e))?;
+        }
+
+        let plaintext = Zeroizing::new(
+            serde_json::to_string(entries)
+                .map_err(|e| format!("Failed to serialize storage: {}", e))?,
+        );
+        let envelope = encrypt_with_key(&plaintext, &self.key)?;
+
+        let file = StorageFile {
+            version: STORAGE_FORMAT_VERSION,
+            envelope,
+        };
+        let serialized = serde_json::to_vec_pretty(&file)
+            .map_err(|e| format!("Failed to serialize storage envelope: {}", e))?;
+
+        // Write-then-rename so readers never observe a partial file.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, &serialized)
+            .map_err(|e| format!("Failed to write storage file: {}", e))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to commit storage file: {}", e))?;
+
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn set(&self, key: String, value: Value) -> Result<(), String> {
+        let mut data = self
+            .data
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        data.insert(key, value);
+        self.persist(&data)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        let data = self
+            .data
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        Ok(data.get(key).cloned())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), String> {
+        let mut data = self
+            .data
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        data.remove(key);
+        self.persist(&data)
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        let mut data = self
+            .data
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        data.clear();
+        self.persist(&data)
+    }
+}
+
+/// Builds the composite key under which an encrypted key for a given
+/// account/role is stored, e.g. `encrypted_key:alice:posting`.
+pub fn encrypted_key_storage_key(username: &str, key_type: &str) -> String {
+    format!("encrypted_key:{}:{}", username, key_type)
+}
+
+/// Which concrete `Storage` backend `StorageManager` should wire up.
+/// Add a variant here as new backends (e.g. an OS keyring) come online.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Persistent, encrypted-on-disk storage under the app data dir.
+    File,
+    /// In-memory only - tests and local development.
+    InMemory,
+}
+
+/// Environment variable `StorageConfig::from_env` reads to override the
+/// default backend, e.g. `STEEM_WALLET_STORAGE_BACKEND=memory` for local
+/// development without touching the app data dir.
+const STORAGE_BACKEND_ENV_VAR: &str = "STEEM_WALLET_STORAGE_BACKEND";
+
+/// Startup configuration for which `StorageBackend` `run()` wires up.
+#[derive(Debug, Clone, Copy)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+}
+
+impl StorageConfig {
+    /// Reads `STEEM_WALLET_STORAGE_BACKEND` (`"file"` | `"memory"`),
+    /// defaulting to the persistent, encrypted file backend.
+    pub fn from_env() -> Self {
+        let backend = match std::env::var(STORAGE_BACKEND_ENV_VAR).as_deref() {
+            Ok("memory") => StorageBackend::InMemory,
+            _ => StorageBackend::File,
+        };
+        StorageConfig { backend }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: StorageBackend::File,
+        }
+    }
+}
+
+/// Shared handle to the active storage backend, managed as Tauri state.
+///
+/// The backend is chosen once, at startup (see `run()` in `lib.rs`, via
+/// `StorageConfig`), and shared behind an `Arc<dyn Storage + Send + Sync>`
+/// so every command handler goes through the same backend-agnostic handle.
+pub struct StorageManager {
+    backend: Arc<dyn Storage + Send + Sync>,
+}
+
+impl StorageManager {
+    /// Wrap an arbitrary backend, e.g. a `FileStorage` rooted at the app
+    /// data dir, or an OS-keyring-backed implementation added later.
+    pub fn new(backend: Arc<dyn Storage + Send + Sync>) -> Self {
+        StorageManager { backend }
+    }
+
+    /// In-memory backend, for tests and `ClearText` mode.
+    pub fn in_memory() -> Self {
+        StorageManager::new(Arc::new(MemoryStorage::new()))
+    }
+
+    /// Persistent, file-backed storage rooted at `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Result<Self, String> {
+        Ok(StorageManager::new(Arc::new(FileStorage::new(path)?)))
+    }
+
+    /// Build the backend selected by `config`, rooting file-backed storage
+    /// under `data_dir`. This is what `run()` calls so the backend is
+    /// picked via builder config rather than hardcoded.
+    pub fn from_config(config: StorageConfig, data_dir: impl Into<PathBuf>) -> Result<Self, String> {
+        match config.backend {
+            StorageBackend::File => {
+                StorageManager::file(data_dir.into().join("wallet-storage.json"))
+            }
+            StorageBackend::InMemory => Ok(StorageManager::in_memory()),
+        }
+    }
+
+    pub fn set(&self, key: String, value: Value) -> Result<(), String> {
+        self.backend.set(key, value)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        self.backend.get(key)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), String> {
+        self.backend.remove(key)
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.backend.clear()
+    }
+}
+
 impl Default for StorageManager {
     fn default() -> Self {
-        Self::new()
+        Self::in_memory()
     }
 }
 
@@ -65,8 +406,8 @@ pub fn storage_set(
     value: String,
     storage: State<StorageManager>,
 ) -> Result<(), String> {
-    let json_value: Value = serde_json::from_str(&value)
-        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    let json_value: Value =
+        serde_json::from_str(&value).map_err(|e| format!("Invalid JSON: {}", e))?;
     storage.set(key, json_value)
 }
 